@@ -22,101 +22,46 @@ impl DependencyGraph {
         }
     }
 
-    /// Returns the first cycle found in the `DependencyGraph`.
-    /// If no cycle exists, then `None` is returned.
-    pub fn find_cycle(&self) -> Option<Vec<String>> {
-        // A cycle exists as the presence of a back edge indicates a cycle in a directed graph
-        if let Some(edge) = self.find_back_edge() {
-            let predecessors = self
-                .find_shortest_path(edge.0.clone(), edge.1.clone())
-                .unwrap();
-
-            return Some(DependencyGraph::reconstruct_cycle_path(
-                edge.0,
-                edge.1,
-                predecessors,
-            ));
+    /// Returns the cycle that adding edge `from -> to` would close, without adding it, by
+    /// checking whether `to` can already reach `from`. Called once per edge while
+    /// incrementally building the graph, so a cycle discovered earlier won't cause later,
+    /// unrelated edges to be misreported as circular.
+    pub fn cycle_for_new_edge(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![from.to_string(), to.to_string()]);
         }
 
-        // No cycle exists
-        None
-    }
-
-    /// Returns the first back edge found in the `DependencyGraph` using DFS.
-    /// If no back edge exists, then `None` is returned.
-    fn find_back_edge(&self) -> Option<(String, String)> {
-        let mut discovered: HashSet<String> = HashSet::new();
-        let mut finished: HashSet<String> = HashSet::new();
+        let mut cycle = self.path_to(to, from)?;
+        cycle.insert(0, from.to_string());
 
-        for vertex in self.graph.keys() {
-            if discovered.contains(vertex) && finished.contains(vertex) {
-                continue;
-            }
-
-            if let Some(edge) = self.dfs_visit(vertex.clone(), &mut discovered, &mut finished) {
-                return Some((edge.0, edge.1));
-            }
-        }
-
-        None
+        Some(cycle)
     }
 
-    /// Returns the first back edge found while analysing a vertex and its children.
-    /// If no back edge exists, then `None` is returned.
-    fn dfs_visit(
-        &self,
-        vertex: String,
-        discovered: &mut HashSet<String>,
-        finished: &mut HashSet<String>,
-    ) -> Option<(String, String)> {
-        discovered.insert(vertex.clone());
-
-        if let Some(children) = &self.graph.get(&vertex) {
-            for child in children.iter() {
-                if discovered.contains(child) {
-                    return Some((child.clone(), vertex));
-                }
-
-                if !finished.contains(child) {
-                    if let Some(edge) = self.dfs_visit(child.to_string(), discovered, finished) {
-                        return Some((edge.0, edge.1));
-                    }
-                }
-            }
-        }
-
-        discovered.remove(&vertex);
-        finished.insert(vertex);
-
-        None
-    }
-
-    /// Returns the predecessors of the shortest path using BFS.
-    /// The predecessors can then be used to reconstruct the path.
-    fn find_shortest_path(&self, start: String, end: String) -> Option<HashMap<String, String>> {
-        let mut queue: VecDeque<String> = VecDeque::from([start.clone()]);
-        let mut visited: HashSet<String> = HashSet::from([start]);
+    /// Returns a path from `from` to `to` found via BFS, or `None` if `to` isn't reachable.
+    fn path_to(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut queue: VecDeque<String> = VecDeque::from([from.to_string()]);
+        let mut visited: HashSet<String> = HashSet::from([from.to_string()]);
         let mut predecessors: HashMap<String, String> = HashMap::new();
 
-        while !queue.is_empty() {
-            let vertex = queue.pop_front().unwrap();
+        while let Some(vertex) = queue.pop_front() {
+            if vertex == to {
+                let mut path = vec![vertex.clone()];
+                let mut crawl = vertex;
 
-            if vertex == end {
-                return Some(predecessors);
-            }
-
-            if let Some(children) = &self.graph.get(&vertex) {
-                for child in children.iter() {
-                    if visited.contains(child) {
-                        continue;
-                    }
+                while let Some(predecessor) = predecessors.get(&crawl) {
+                    path.push(predecessor.clone());
+                    crawl = predecessor.clone();
+                }
 
-                    queue.push_back(child.clone());
-                    visited.insert(child.clone());
-                    predecessors.insert(child.to_string(), vertex.clone());
+                path.reverse();
+                return Some(path);
+            }
 
-                    if child == &end {
-                        return Some(predecessors);
+            if let Some(children) = self.graph.get(&vertex) {
+                for child in children {
+                    if visited.insert(child.clone()) {
+                        predecessors.insert(child.clone(), vertex.clone());
+                        queue.push_back(child.clone());
                     }
                 }
             }
@@ -124,27 +69,6 @@ impl DependencyGraph {
 
         None
     }
-
-    /// Returns the reconstructed cycle path from vertex `start` to vertex `end`.
-    /// The vertex `start` will be present twice, at the beginning and at the end of the result.
-    fn reconstruct_cycle_path(
-        start: String,
-        end: String,
-        predecessors: HashMap<String, String>,
-    ) -> Vec<String> {
-        let mut path: Vec<String> = Vec::from([end.clone()]);
-        let mut crawl = end;
-
-        while let Some(predecessor) = predecessors.get(&crawl) {
-            path.push(predecessor.to_string());
-            crawl = predecessor.to_string();
-        }
-
-        path.reverse();
-        path.push(start);
-
-        path
-    }
 }
 
 #[cfg(test)]
@@ -152,49 +76,52 @@ mod test {
     use super::*;
 
     #[test]
-    fn contains_no_cycle_when_empty() {
+    fn cycle_for_new_edge_reports_a_self_edge() {
         let dependency_graph = DependencyGraph::new();
-        let cycle = dependency_graph.find_cycle();
 
-        assert_eq!(cycle, None);
+        let cycle = dependency_graph.cycle_for_new_edge("A", "A");
+
+        assert_eq!(cycle, Some(vec![String::from("A"), String::from("A")]));
     }
 
     #[test]
-    fn contains_no_cycle_when_no_back_edge() {
+    fn cycle_for_new_edge_reports_an_edge_that_closes_a_cycle() {
         let mut dependency_graph = DependencyGraph::new();
 
-        dependency_graph.add_edge(String::from("A"), String::from("B"));
-        dependency_graph.add_edge(String::from("B"), String::from("C"));
-        dependency_graph.add_edge(String::from("C"), String::from("D"));
-        dependency_graph.add_edge(String::from("A"), String::from("D"));
+        dependency_graph.add_edge(String::from("B"), String::from("D"));
 
-        let cycle = dependency_graph.find_cycle();
+        let cycle = dependency_graph.cycle_for_new_edge("D", "B");
 
-        assert_eq!(cycle, None);
+        assert_eq!(
+            cycle,
+            Some(vec![String::from("D"), String::from("B"), String::from("D")])
+        );
     }
 
     #[test]
-    fn contains_cycle_when_loop() {
+    fn cycle_for_new_edge_ignores_an_edge_that_does_not_close_a_cycle() {
         let mut dependency_graph = DependencyGraph::new();
 
-        dependency_graph.add_edge(String::from("A"), String::from("A"));
+        dependency_graph.add_edge(String::from("A"), String::from("B"));
+        dependency_graph.add_edge(String::from("B"), String::from("C"));
 
-        let cycle = dependency_graph.find_cycle().unwrap();
+        let cycle = dependency_graph.cycle_for_new_edge("C", "D");
 
-        assert_eq!(cycle, vec![String::from("A"), String::from("A")]);
+        assert_eq!(cycle, None);
     }
 
     #[test]
-    fn contains_cycle_when_back_edge() {
+    fn a_cycle_found_earlier_does_not_shadow_a_later_unrelated_edge() {
         let mut dependency_graph = DependencyGraph::new();
 
-        dependency_graph.add_edge(String::from("A"), String::from("B"));
-        dependency_graph.add_edge(String::from("B"), String::from("C"));
-        dependency_graph.add_edge(String::from("C"), String::from("B"));
-        dependency_graph.add_edge(String::from("C"), String::from("D"));
+        dependency_graph.add_edge(String::from("B"), String::from("D"));
+        let first_cycle = dependency_graph.cycle_for_new_edge("D", "B");
+        dependency_graph.add_edge(String::from("D"), String::from("B"));
+
+        assert!(first_cycle.is_some());
 
-        let cycle = dependency_graph.find_cycle().unwrap();
+        let later_cycle = dependency_graph.cycle_for_new_edge("C", "E");
 
-        assert_eq!(cycle.len(), 3);
+        assert_eq!(later_cycle, None);
     }
 }
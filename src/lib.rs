@@ -21,12 +21,15 @@ mod dependency_graph;
 
 use dependency_graph::DependencyGraph;
 use lazy_static::lazy_static;
-use proc_macro::{Literal, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Literal, TokenStream, TokenTree};
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs::{canonicalize, read_to_string};
+use std::io;
 use std::path::{Path, PathBuf};
 
-fn resolve_path(path: &str, parent_dir_path: Option<PathBuf>) -> PathBuf {
+fn resolve_path(path: &str, parent_dir_path: Option<PathBuf>) -> io::Result<PathBuf> {
     let mut path = PathBuf::from(path);
 
     if let Some(p) = parent_dir_path {
@@ -34,13 +37,58 @@ fn resolve_path(path: &str, parent_dir_path: Option<PathBuf>) -> PathBuf {
             path = p.join(path);
         }
     }
-    
-    canonicalize(&path).unwrap_or_else(|e| {
-        panic!(
-            "An error occured while trying to resolve path: {:?}. Error: {}",
-            path, e
-        )
-    })
+
+    canonicalize(&path)
+}
+
+/// Resolves an angle-bracket `#include <name>` against an ordered list of include search
+/// directories, the way a C preprocessor resolves system includes: each directory is tried in
+/// turn and the first one that canonicalizes successfully wins.
+fn resolve_angle_include(path: &str, include_dirs: &[PathBuf]) -> io::Result<PathBuf> {
+    for dir in include_dirs {
+        if let Ok(resolved) = canonicalize(dir.join(path)) {
+            return Ok(resolved);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "not found in any of the {} configured include director{}",
+            include_dirs.len(),
+            if include_dirs.len() == 1 { "y" } else { "ies" }
+        ),
+    ))
+}
+
+/// Resolves an `include_dirs` entry to an absolute directory, following the same
+/// relative-path/workspace-root convention as the macro's own path argument.
+fn prepare_include_dir(dir: &str, parent_dir_path: &Option<PathBuf>) -> PathBuf {
+    let mut path = PathBuf::from(dir);
+
+    if !path.is_absolute() {
+        if let Some(p) = parent_dir_path {
+            path = p.join(path);
+        }
+    }
+
+    path
+}
+
+/// Builds the ordered list of include search directories from the `include_dirs` macro
+/// argument followed by the `INCLUDE_SHADER_DIRS` environment variable (read at
+/// macro-expansion time, entries separated the same way as `PATH`), if set.
+fn collect_include_dirs(configured: &[String], parent_dir_path: &Option<PathBuf>) -> Vec<PathBuf> {
+    let mut include_dirs: Vec<PathBuf> = configured
+        .iter()
+        .map(|dir| prepare_include_dir(dir, parent_dir_path))
+        .collect();
+
+    if let Ok(env_dirs) = env::var("INCLUDE_SHADER_DIRS") {
+        include_dirs.extend(env::split_paths(&env_dirs));
+    }
+
+    include_dirs
 }
 
 fn track_file(_path: &Path) {
@@ -48,59 +96,359 @@ fn track_file(_path: &Path) {
     proc_macro::tracked_path::path(_path.to_string_lossy());
 }
 
-fn process_file(path: &Path, dependency_graph: &mut DependencyGraph) -> String {
-    let content = read_to_string(path).unwrap_or_else(|e| {
-        panic!(
-            "An error occured while trying to read file: {}. Error: {}",
-            path.to_string_lossy(),
-            e
-        )
-    });
+/// Returns the directory of the file calling the macro when the `relative-path` feature is
+/// enabled, so the macro's own path argument can be resolved relative to it.
+fn relative_path_parent_dir() -> Option<PathBuf> {
+    #[cfg(feature = "relative-path")] {
+        let mut path = proc_macro::Span::call_site().source_file().path();
+        path.pop();
+        Some(path)
+    }
+
+    #[cfg(not(feature = "relative-path"))]
+    None
+}
+
+/// The form of the `#line` directives injected around spliced-in includes.
+///
+/// `Integer` emits the GLSL-standard `#line <line> <source_string_number>`, which every
+/// driver accepts. `String` emits `#line <line> "<path>"`, which maps errors straight back
+/// to a real file path but requires the `GL_ARB_shading_language_include` extension.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LineDirectiveStyle {
+    Integer,
+    String,
+}
+
+/// Assigns a stable synthetic "source string number" to each file touched while resolving
+/// `#include`s, and remembers the `PathBuf` each number came from so tooling can translate a
+/// compiler's `0:42`-style error location back to the file the user actually wrote.
+#[derive(Default)]
+struct LineMap {
+    indices: HashMap<PathBuf, usize>,
+    paths: Vec<PathBuf>,
+}
+
+impl LineMap {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn index_for(&mut self, path: &Path) -> usize {
+        if let Some(index) = self.indices.get(path) {
+            return *index;
+        }
+
+        let index = self.paths.len();
+        self.paths.push(path.to_path_buf());
+        self.indices.insert(path.to_path_buf(), index);
+        index
+    }
+
+    /// Returns the `(source_string_index, path)` table, in index order.
+    fn into_table(self) -> Vec<(usize, PathBuf)> {
+        self.paths.into_iter().enumerate().collect()
+    }
+}
+
+fn line_directive(style: LineDirectiveStyle, line: usize, path: &Path, index: usize) -> String {
+    match style {
+        LineDirectiveStyle::Integer => format!("#line {} {}\n", line, index),
+        LineDirectiveStyle::String => format!("#line {} \"{}\"\n", line, path.to_string_lossy()),
+    }
+}
+
+/// A single problem found while walking the include tree. Diagnostics are accumulated as the
+/// whole tree is walked instead of panicking on the first one, so a broken shader tree can be
+/// fixed in one pass instead of one rebuild per error.
+enum Diagnostic {
+    UnresolvedRoot {
+        path: String,
+        reason: String,
+    },
+    UnresolvedInclude {
+        /// The chain of files, root first, ending with the file containing the directive.
+        chain: Vec<PathBuf>,
+        offset: usize,
+        path: String,
+        reason: String,
+    },
+    CircularDependency {
+        chain: Vec<PathBuf>,
+        offset: usize,
+        cycle: Vec<String>,
+    },
+}
+
+impl Diagnostic {
+    fn message(&self) -> String {
+        match self {
+            Diagnostic::UnresolvedRoot { path, reason } => {
+                format!("could not resolve shader path \"{}\": {}", path, reason)
+            }
+            Diagnostic::UnresolvedInclude { chain, offset, path, reason } => format!(
+                "could not resolve #include \"{}\" (byte offset {} in {}): {}. Include chain: {}",
+                path,
+                offset,
+                chain.last().unwrap().to_string_lossy(),
+                reason,
+                format_chain(chain),
+            ),
+            Diagnostic::CircularDependency { chain, offset, cycle } => format!(
+                "circular dependency detected at byte offset {} in {}: {}. Include chain: {}",
+                offset,
+                chain.last().unwrap().to_string_lossy(),
+                cycle.join(" -> "),
+                format_chain(chain),
+            ),
+        }
+    }
+}
+
+fn format_chain(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Turns every accumulated [`Diagnostic`] into a single `compile_error!` invocation, so a
+/// broken shader tree is reported in one pass instead of one error per rebuild.
+fn diagnostics_to_compile_error(diagnostics: &[Diagnostic]) -> TokenStream {
+    let message = diagnostics
+        .iter()
+        .map(Diagnostic::message)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("compile_error!({:?})", message).parse().unwrap()
+}
+
+/// Resolves `arg` to a root shader file and fully expands its `#include` tree, collecting
+/// every problem encountered along the way instead of stopping at the first one.
+fn process_shader_tree(
+    arg: &str,
+    config: &MacroConfig,
+) -> Result<(String, LineMap), Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let parent_dir_path = relative_path_parent_dir();
+
+    let root_path = match resolve_path(arg, parent_dir_path.clone()) {
+        Ok(root_path) => root_path,
+        Err(e) => {
+            diagnostics.push(Diagnostic::UnresolvedRoot {
+                path: arg.to_string(),
+                reason: e.to_string(),
+            });
+            return Err(diagnostics);
+        }
+    };
+
+    let include_dirs = collect_include_dirs(&config.include_dirs, &parent_dir_path);
+    let mut dependency_graph = DependencyGraph::new();
+    let mut included = HashSet::new();
+    let mut line_map = LineMap::new();
+    let chain = vec![root_path.clone()];
+
+    let result = process_file(
+        &root_path,
+        &mut dependency_graph,
+        &mut included,
+        &mut line_map,
+        config.line_directive_style,
+        &include_dirs,
+        &chain,
+        &mut diagnostics,
+    );
+
+    match result {
+        Ok(content) if diagnostics.is_empty() => Ok((content, line_map)),
+        Ok(_) => Err(diagnostics),
+        Err(e) => {
+            diagnostics.push(Diagnostic::UnresolvedRoot {
+                path: root_path.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            });
+            Err(diagnostics)
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_file(
+    path: &Path,
+    dependency_graph: &mut DependencyGraph,
+    included: &mut HashSet<PathBuf>,
+    line_map: &mut LineMap,
+    line_directive_style: LineDirectiveStyle,
+    include_dirs: &[PathBuf],
+    chain: &[PathBuf],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<String, io::Error> {
+    let content = read_to_string(path)?;
 
     track_file(path);
 
-    process_includes(path, content, dependency_graph)
+    lazy_static! {
+        static ref PRAGMA_ONCE_RE: Regex = Regex::new(r"(?m)^[ \t]*#pragma[ \t]+once[ \t]*").unwrap();
+    }
+
+    let content = process_includes(
+        path,
+        content,
+        dependency_graph,
+        included,
+        line_map,
+        line_directive_style,
+        include_dirs,
+        chain,
+        diagnostics,
+    );
+
+    // Stripped only after the includes are scanned, and with its line left in place (empty),
+    // so diagnostic byte offsets and `#line` numbers still refer to the file as written.
+    Ok(PRAGMA_ONCE_RE.replace_all(&content, "").into_owned())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_includes(
     source_path: &Path,
     source_file_content: String,
     dependency_graph: &mut DependencyGraph,
+    included: &mut HashSet<PathBuf>,
+    line_map: &mut LineMap,
+    line_directive_style: LineDirectiveStyle,
+    include_dirs: &[PathBuf],
+    chain: &[PathBuf],
+    diagnostics: &mut Vec<Diagnostic>,
 ) -> String {
     lazy_static! {
-        static ref INCLUDE_RE: Regex = Regex::new(r#"#include\s+"(?P<file>.*)""#).unwrap();
+        // Quoted `#include "file"` resolves relative to the current file/workspace root, like
+        // today. Angle-bracket `#include <file>` resolves against `include_dirs` instead, the
+        // way a C preprocessor resolves system includes.
+        static ref INCLUDE_RE: Regex =
+            Regex::new(r#"#include\s+(?:"(?P<quoted>[^"]*)"|<(?P<angle>[^>]*)>)"#).unwrap();
     }
-    let mut result = source_file_content;
 
-    while let Some(captures) = INCLUDE_RE.captures(&result.clone()) {
+    let source_index = line_map.index_for(source_path);
+    let mut result = String::with_capacity(source_file_content.len());
+    let mut cursor = 0;
+    let mut line = 1;
+
+    for captures in INCLUDE_RE.captures_iter(&source_file_content) {
         let capture = captures.get(0).unwrap();
+        let (raw_path, is_angle) = match captures.name("quoted") {
+            Some(m) => (m.as_str(), false),
+            None => (captures.name("angle").unwrap().as_str(), true),
+        };
+
+        let before = &source_file_content[cursor..capture.start()];
+        result.push_str(before);
+        line += before.matches('\n').count();
+        cursor = capture.end();
+
+        let include_path = if is_angle {
+            resolve_angle_include(raw_path, include_dirs)
+        } else {
+            #[allow(unused_assignments, unused_mut)]
+            let mut include_parent_dir_path = None;
+
+            #[cfg(feature = "relative-path")] {
+                let mut path = source_path.to_path_buf();
+                path.pop();
+                include_parent_dir_path = Some(path);
+            }
+
+            resolve_path(raw_path, include_parent_dir_path)
+        };
 
-        #[allow(unused_assignments, unused_mut)]
-        let mut include_parent_dir_path = None;
-        
-        #[cfg(feature = "relative-path")] {
-            let mut path = source_path.to_path_buf();
-            path.pop();
-            include_parent_dir_path = Some(path);
+        let include_path = match include_path {
+            Ok(include_path) => include_path,
+            Err(e) => {
+                diagnostics.push(Diagnostic::UnresolvedInclude {
+                    chain: chain.to_vec(),
+                    offset: capture.start(),
+                    path: raw_path.to_string(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let source_key = source_path.to_string_lossy().to_string();
+        let include_key = include_path.to_string_lossy().to_string();
+
+        // Check before adding the edge, and only for the cycle this specific edge would
+        // close, so a cycle found earlier in the tree doesn't shadow unrelated, healthy
+        // includes discovered afterwards.
+        let new_cycle = dependency_graph.cycle_for_new_edge(&source_key, &include_key);
+        dependency_graph.add_edge(source_key, include_key);
+
+        if let Some(cycle) = new_cycle {
+            diagnostics.push(Diagnostic::CircularDependency {
+                chain: chain.to_vec(),
+                offset: capture.start(),
+                cycle,
+            });
+            continue;
+        }
+
+        // Once a file has been inlined, later `#include`s of the same canonical path
+        // (diamond includes, or files guarded with `#pragma once`) are dropped instead
+        // of being expanded again, which would otherwise duplicate definitions.
+        if included.contains(&include_path) {
+            continue;
         }
-        
-        let include_path = resolve_path(captures.name("file").unwrap().as_str(), include_parent_dir_path);
+        included.insert(include_path.clone());
 
-        dependency_graph.add_edge(
-            source_path.to_string_lossy().to_string(),
-            include_path.to_string_lossy().to_string(),
-        );
+        let mut include_chain = chain.to_vec();
+        include_chain.push(include_path.clone());
 
-        if let Some(cycle) = dependency_graph.find_cycle() {
-            panic!("Circular dependency detected: {}", cycle.join(" -> "));
+        let include_index = line_map.index_for(&include_path);
+        let included_content = match process_file(
+            &include_path,
+            dependency_graph,
+            included,
+            line_map,
+            line_directive_style,
+            include_dirs,
+            &include_chain,
+            diagnostics,
+        ) {
+            Ok(content) => content,
+            Err(e) => {
+                diagnostics.push(Diagnostic::UnresolvedInclude {
+                    chain: chain.to_vec(),
+                    offset: capture.start(),
+                    path: raw_path.to_string(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        // Wrap the spliced-in content with `#line` directives so a compiler's line/string
+        // numbers in the flattened blob still map back to the file that produced them.
+        result.push_str(&line_directive(line_directive_style, 1, &include_path, include_index));
+        result.push_str(&included_content);
+        if !included_content.ends_with('\n') {
+            result.push('\n');
         }
 
-        result.replace_range(
-            capture.start()..capture.end(),
-            &process_file(&include_path, dependency_graph),
-        );
+        // Drop the newline ending the `#include` line, so the directive below numbers the
+        // line that really follows it.
+        let rest = &source_file_content[cursor..];
+        let rest_trimmed = rest.trim_start_matches(|c: char| c == ' ' || c == '\t' || c == '\r');
+        if rest_trimmed.starts_with('\n') {
+            cursor += rest.len() - rest_trimmed.len() + 1;
+            line += 1;
+        }
+        result.push_str(&line_directive(line_directive_style, line, source_path, source_index));
     }
 
+    result.push_str(&source_file_content[cursor..]);
+
     result
 }
 
@@ -114,11 +462,213 @@ fn expr_to_string(expr: &Literal) -> Option<String> {
     Some(expr)
 }
 
-fn get_single_string_from_token_stream(token_stream: TokenStream) -> Option<String> {
-    let tokens: Vec<_> = token_stream.into_iter().collect();
-    match tokens.as_slice() {
-        [TokenTree::Literal(expr)] => expr_to_string(expr),
-        _ => None,
+/// Splits a flat list of tokens into groups separated by top-level commas,
+/// leaving the contents of groups (`(...)`, `[...]`, `{...}`) untouched.
+fn split_top_level_commas(tokens: Vec<TokenTree>) -> Vec<Vec<TokenTree>> {
+    let mut groups = Vec::new();
+    let mut current = Vec::new();
+
+    for token in tokens {
+        if let TokenTree::Punct(punct) = &token {
+            if punct.as_char() == ',' {
+                groups.push(std::mem::take(&mut current));
+                continue;
+            }
+        }
+        current.push(token);
+    }
+
+    if !current.is_empty() {
+        groups.push(current);
+    }
+
+    groups
+}
+
+fn single_literal_string(tokens: &[TokenTree], what: &str) -> String {
+    match tokens {
+        [TokenTree::Literal(literal)] => expr_to_string(literal)
+            .unwrap_or_else(|| panic!("Expected {} to be a string literal", what)),
+        _ => panic!("Expected {} to be a single string literal", what),
+    }
+}
+
+/// Splits `name = value` into the argument name and the remaining value tokens.
+fn parse_named_arg(tokens: &[TokenTree]) -> (String, Vec<TokenTree>) {
+    let name = match tokens.first() {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => panic!("Expected a named argument, e.g. `version = 330`"),
+    };
+
+    match tokens.get(1) {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '=' => {}
+        _ => panic!("Expected `=` after argument name `{}`", name),
+    }
+
+    (name, tokens[2..].to_vec())
+}
+
+fn expect_bracket_group(tokens: &[TokenTree], arg_name: &str) -> Vec<TokenTree> {
+    match tokens {
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Bracket => {
+            group.stream().into_iter().collect()
+        }
+        _ => panic!(
+            "Argument `{}` must be an array literal, e.g. `{} = [...]`",
+            arg_name, arg_name
+        ),
+    }
+}
+
+fn parse_version(tokens: &[TokenTree]) -> String {
+    match tokens {
+        [TokenTree::Literal(literal)] => {
+            let version = literal.to_string();
+            if version.parse::<u32>().is_err() {
+                panic!("Argument `version` must be an integer literal, e.g. `version = 330`");
+            }
+            version
+        }
+        _ => panic!("Argument `version` must be an integer literal, e.g. `version = 330`"),
+    }
+}
+
+/// Parses `defines = [("NAME", "VALUE"), "FLAG"]` into a list of define
+/// names paired with an optional value, `FLAG`-only defines having `None`.
+fn parse_defines(tokens: &[TokenTree]) -> Vec<(String, Option<String>)> {
+    let items = expect_bracket_group(tokens, "defines");
+
+    split_top_level_commas(items)
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| match group.as_slice() {
+            [TokenTree::Literal(name)] => {
+                let name = expr_to_string(name)
+                    .unwrap_or_else(|| panic!("Define name must be a string literal"));
+                (name, None)
+            }
+            [TokenTree::Group(pair)] if pair.delimiter() == Delimiter::Parenthesis => {
+                let pair_tokens: Vec<TokenTree> = pair.stream().into_iter().collect();
+                match split_top_level_commas(pair_tokens).as_slice() {
+                    [name, value] => (
+                        single_literal_string(name, "define name"),
+                        Some(single_literal_string(value, "define value")),
+                    ),
+                    _ => panic!("Expected a `(\"NAME\", \"VALUE\")` pair in `defines`"),
+                }
+            }
+            _ => panic!("Expected a string literal or a `(\"NAME\", \"VALUE\")` pair in `defines`"),
+        })
+        .collect()
+}
+
+/// Parses an array of string literals, e.g. `extensions = ["GL_OES_standard_derivatives"]` or
+/// `include_dirs = ["src/shaders/lib", "vendor/glsl"]`.
+fn parse_string_array(tokens: &[TokenTree], arg_name: &str, item_name: &str) -> Vec<String> {
+    let items = expect_bracket_group(tokens, arg_name);
+
+    split_top_level_commas(items)
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| single_literal_string(&group, item_name))
+        .collect()
+}
+
+fn parse_line_directive_style(tokens: &[TokenTree]) -> LineDirectiveStyle {
+    match single_literal_string(tokens, "line_directive_style").as_str() {
+        "integer" => LineDirectiveStyle::Integer,
+        "string" => LineDirectiveStyle::String,
+        other => panic!(
+            "Argument `line_directive_style` must be `\"integer\"` or `\"string\"`, got `\"{}\"`",
+            other
+        ),
+    }
+}
+
+/// The named arguments accepted after the shader path: the preamble settings an autoloader
+/// would normally inject (`#version`, `#extension`, `#define`, `precision`), plus
+/// `line_directive_style` to pick the `#line` directive form used when splicing includes, and
+/// `include_dirs` to resolve angle-bracket `#include <...>` directives.
+struct MacroConfig {
+    version: Option<String>,
+    extensions: Vec<String>,
+    defines: Vec<(String, Option<String>)>,
+    precision: Option<String>,
+    line_directive_style: LineDirectiveStyle,
+    include_dirs: Vec<String>,
+}
+
+impl Default for MacroConfig {
+    fn default() -> Self {
+        Self {
+            version: None,
+            extensions: Vec::new(),
+            defines: Vec::new(),
+            precision: None,
+            line_directive_style: LineDirectiveStyle::Integer,
+            include_dirs: Vec::new(),
+        }
+    }
+}
+
+impl MacroConfig {
+    /// Parses the named arguments following the shader path, e.g.
+    /// `version = 330, defines = [("MAX_LIGHTS", "8")]`.
+    fn parse(groups: &[Vec<TokenTree>]) -> Self {
+        let mut config = MacroConfig::default();
+
+        for group in groups {
+            let (name, value_tokens) = parse_named_arg(group);
+
+            match name.as_str() {
+                "version" => config.version = Some(parse_version(&value_tokens)),
+                "extensions" => {
+                    config.extensions = parse_string_array(&value_tokens, "extensions", "extension name")
+                }
+                "defines" => config.defines = parse_defines(&value_tokens),
+                "precision" => config.precision = Some(single_literal_string(&value_tokens, "precision")),
+                "line_directive_style" => {
+                    config.line_directive_style = parse_line_directive_style(&value_tokens)
+                }
+                "include_dirs" => {
+                    config.include_dirs = parse_string_array(&value_tokens, "include_dirs", "include directory")
+                }
+                _ => panic!(
+                    "Unknown argument `{}`, expected one of: version, defines, extensions, \
+                     precision, line_directive_style, include_dirs",
+                    name
+                ),
+            }
+        }
+
+        config
+    }
+
+    /// Builds the preamble in the order GLSL requires it: `#version` first,
+    /// then `#extension`, then `#define`, then `precision`.
+    fn build_preamble(&self) -> String {
+        let mut preamble = String::new();
+
+        if let Some(version) = &self.version {
+            preamble.push_str(&format!("#version {}\n", version));
+        }
+
+        for extension in &self.extensions {
+            preamble.push_str(&format!("#extension {} : enable\n", extension));
+        }
+
+        for (name, value) in &self.defines {
+            match value {
+                Some(value) => preamble.push_str(&format!("#define {} {}\n", name, value)),
+                None => preamble.push_str(&format!("#define {}\n", name)),
+            }
+        }
+
+        if let Some(precision) = &self.precision {
+            preamble.push_str(&format!("precision {};\n", precision));
+        }
+
+        preamble
     }
 }
 
@@ -128,11 +678,11 @@ fn get_single_string_from_token_stream(token_stream: TokenStream) -> Option<Stri
 /// If the `relative-path` feature is enabled, then the file is located relative
 /// to the current file.
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if:
-/// * A file specified cannot be found
-/// * A circular dependency is detected
+/// Emits a `compile_error!` listing every problem found while walking the include tree
+/// (missing or unreadable files, circular dependencies) rather than stopping at the first
+/// one, so a broken shader tree can be fixed in a single pass.
 ///
 /// # Examples
 ///
@@ -153,6 +703,9 @@ fn get_single_string_from_token_stream(token_stream: TokenStream) -> Option<Stri
 /// ## Dependencies
 ///
 /// Dependencies are supported within shader files using the `#include` preprocessor directive.
+/// A file that is reachable through more than one include path (e.g. two files that both
+/// include `utils.glsl`) is only inlined once; `#pragma once` is also recognized and
+/// stripped from the output, for compatibility with files that already carry it.
 ///
 /// `rand.glsl`:
 ///
@@ -175,25 +728,104 @@ fn get_single_string_from_token_stream(token_stream: TokenStream) -> Option<Stri
 ///    gl_FragColor = vec4(vec3(rand(st)), 1.0);
 /// }
 /// ```
+///
+/// ## Preamble
+///
+/// A GLSL preamble can be generated ahead of the resolved source by passing optional
+/// named arguments after the path, mirroring what an autoloader would normally inject:
+///
+/// ```ignore
+/// let frag_shader = include_shader!(
+///     "src/shaders/fragment_shader.glsl",
+///     version = 330,
+///     defines = [("MAX_LIGHTS", "8"), "USE_SHADOWS"],
+///     extensions = ["GL_OES_standard_derivatives"],
+///     precision = "highp float",
+/// );
+/// ```
+///
+/// The generated preamble is emitted, in order, as the `#version` line, the `#extension`
+/// directives, the `#define` directives, then the `precision` qualifier, before the
+/// fully-inlined source.
+///
+/// ## Error locations
+///
+/// Each spliced-in include is wrapped in `#line` directives, so a compiler error reported
+/// against the flattened blob still carries line numbers relative to the file it actually
+/// came from. By default the directives use the integer `#line <line> <source_string_number>`
+/// form every driver accepts; passing `line_directive_style = "string"` switches to
+/// `#line <line> "<path>"`, which requires `GL_ARB_shading_language_include`. Use
+/// [`include_shader_line_map!`] to recover which path a given source string number came from.
+///
+/// ## Include search directories
+///
+/// Quoted `#include "file"` resolves relative to the current file (or the workspace root,
+/// see above), same as ever. Angle-bracket `#include <file>` instead resolves against an
+/// ordered list of include search directories, the way a C preprocessor resolves system
+/// includes, letting a shared shader library live outside the consuming crate:
+///
+/// ```ignore
+/// let frag_shader = include_shader!(
+///     "src/shaders/fragment_shader.glsl",
+///     include_dirs = ["src/shaders/lib", "vendor/glsl"],
+/// );
+/// ```
+///
+/// Each directory is tried in order and the first one the file canonicalizes against wins.
+/// The `INCLUDE_SHADER_DIRS` environment variable, if set when the macro expands, is read the
+/// same way as `PATH` and its entries are appended after `include_dirs`.
 #[proc_macro]
 pub fn include_shader(input: TokenStream) -> TokenStream {
-    let arg = match get_single_string_from_token_stream(input) {
-        Some(string) => string,
-        None => panic!("Takes 1 argument and the argument must be a string literal"),
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let groups = split_top_level_commas(tokens);
+
+    let (path_group, config_groups) = match groups.split_first() {
+        Some(split) => split,
+        None => panic!("Takes at least 1 argument and the first argument must be a string literal"),
     };
 
-    #[allow(unused_assignments, unused_mut)]
-    let mut call_parent_dir_path = None;
+    let arg = single_literal_string(path_group, "the first argument");
+    let config = MacroConfig::parse(config_groups);
 
-    #[cfg(feature = "relative-path")] {
-        let mut path = proc_macro::Span::call_site().source_file().path();
-        path.pop();
-        call_parent_dir_path = Some(path);
-    }
+    let source = match process_shader_tree(&arg, &config) {
+        Ok((source, _line_map)) => source,
+        Err(diagnostics) => return diagnostics_to_compile_error(&diagnostics),
+    };
 
-    let root_path = resolve_path(&arg, call_parent_dir_path);
-    let mut dependency_graph = DependencyGraph::new();
-    let result = process_file(&root_path, &mut dependency_graph);
+    let result = format!("{}{}", config.build_preamble(), source);
 
     format!("{:?}", result).parse().unwrap()
 }
+
+/// Companion macro to [`include_shader!`] that returns the `#line` source-string table built
+/// while resolving `#include`s, as a `&[(u32, &str)]` slice of synthetic source-string index
+/// paired with the real file path it came from. Takes the shader path and the same named
+/// arguments as [`include_shader!`]; arguments that only affect the preamble (`version`,
+/// `defines`, `extensions`, `precision`) are accepted but have no effect here, since they
+/// don't introduce new source files.
+#[proc_macro]
+pub fn include_shader_line_map(input: TokenStream) -> TokenStream {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let groups = split_top_level_commas(tokens);
+
+    let (path_group, config_groups) = match groups.split_first() {
+        Some(split) => split,
+        None => panic!("Takes at least 1 argument and the first argument must be a string literal"),
+    };
+
+    let arg = single_literal_string(path_group, "the first argument");
+    let config = MacroConfig::parse(config_groups);
+
+    let line_map = match process_shader_tree(&arg, &config) {
+        Ok((_source, line_map)) => line_map,
+        Err(diagnostics) => return diagnostics_to_compile_error(&diagnostics),
+    };
+
+    let entries: Vec<String> = line_map
+        .into_table()
+        .into_iter()
+        .map(|(index, path)| format!("({}u32, {:?})", index, path.to_string_lossy()))
+        .collect();
+
+    format!("&[{}]", entries.join(", ")).parse().unwrap()
+}
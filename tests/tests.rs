@@ -1,4 +1,4 @@
-use include_shader::include_shader;
+use include_shader::{include_shader, include_shader_line_map};
 
 #[test]
 fn includes_empty_file() {
@@ -23,3 +23,69 @@ fn includes_file_with_includes() {
     assert!(shader.contains(include_str!("shaders/functions/luminance.glsl")));
     assert!(shader.contains(include_str!("shaders/functions/rand.glsl")));
 }
+
+#[test]
+fn generates_preamble_from_named_arguments() {
+    let shader = include_shader!(
+        "tests/shaders/preamble.glsl",
+        version = 330,
+        extensions = ["GL_OES_standard_derivatives"],
+        defines = [("MAX_LIGHTS", "8"), "USE_SHADOWS"],
+        precision = "highp float",
+    );
+
+    assert_eq!(
+        shader,
+        "#version 330\n#extension GL_OES_standard_derivatives : enable\n#define MAX_LIGHTS 8\n#define USE_SHADOWS\nprecision highp float;\n"
+            .to_string()
+            + include_str!("shaders/preamble.glsl")
+    );
+}
+
+#[test]
+fn dedupes_diamond_includes_and_strips_pragma_once() {
+    let shader = include_shader!("tests/shaders/diamond/root.glsl");
+
+    assert_eq!(shader.matches("shared_value").count(), 1);
+    assert!(!shader.contains("#pragma once"));
+}
+
+#[test]
+fn injects_line_directives_around_spliced_includes() {
+    let shader = include_shader!("tests/shaders/line_map/root.glsl");
+
+    assert!(shader.contains("#line 1 1"));
+    assert!(shader.contains("#line 2 0"));
+}
+
+#[test]
+fn line_map_reports_source_string_paths() {
+    let line_map = include_shader_line_map!("tests/shaders/line_map/root.glsl");
+
+    assert_eq!(line_map[0].0, 0);
+    assert!(line_map[0].1.ends_with("root.glsl"));
+    assert_eq!(line_map[1].0, 1);
+    assert!(line_map[1].1.ends_with("inner.glsl"));
+}
+
+// The diagnostics-accumulation path itself (multiple unresolved includes collapsed into one
+// `compile_error!`) can't be exercised by a normal `#[test]`, since triggering it fails the
+// whole test binary's compilation; this only checks that walking the tree goes on past the
+// first include, so every sibling include of the root is inlined.
+#[test]
+fn resolves_every_include_in_a_tree_with_multiple_children() {
+    let shader = include_shader!("tests/shaders/multi_include/root.glsl");
+
+    assert!(shader.contains(include_str!("shaders/multi_include/one.glsl")));
+    assert!(shader.contains(include_str!("shaders/multi_include/two.glsl")));
+}
+
+#[test]
+fn resolves_angle_bracket_includes_against_include_dirs() {
+    let shader = include_shader!(
+        "tests/shaders/angle/root.glsl",
+        include_dirs = ["tests/shaders/lib"],
+    );
+
+    assert!(shader.contains(include_str!("shaders/lib/common.glsl")));
+}